@@ -0,0 +1,318 @@
+#![feature(rustc_private)]
+#![warn(unused_extern_crates)]
+
+extern crate rustc_hir;
+extern crate rustc_middle;
+extern crate rustc_span;
+
+use clippy_utils::{
+    diagnostics::span_lint, match_any_def_paths, match_def_path, ty::match_type, SpanlessEq,
+};
+use if_chain::if_chain;
+use rustc_hir::{
+    def_id::LocalDefId,
+    intravisit::{walk_expr, FnKind, Visitor},
+    BinOpKind, Body, Expr, ExprKind, FnDecl, MatchSource,
+};
+use rustc_lint::{LateContext, LateLintPass};
+use rustc_middle::ty;
+use rustc_span::Span;
+use solana_lints::{paths, utils::visit_expr_no_bodies};
+
+dylint_linting::declare_late_lint! {
+    /// **What it does:**
+    ///
+    /// This lint checks that the program account passed to `invoke`/`invoke_signed` has its key
+    /// checked against the program id the caller expects to invoke.
+    ///
+    /// **Why is this bad?**
+    ///
+    /// The arbitrary-CPI vulnerability occurs when a program performs a cross-program invocation
+    /// into an account taken from its instruction inputs without verifying that the account is
+    /// actually the program the code intends to call. A malicious actor could substitute their
+    /// own program account (e.g. a fake SPL Token program) for the expected one, and the
+    /// invocation would hand control to attacker-controlled code.
+    ///
+    /// **Known problems:**
+    ///
+    /// The program account is identified from the `Instruction` argument passed to
+    /// `invoke`/`invoke_signed`: either the first argument of an instruction-builder call (e.g.
+    /// `spl_token::instruction::transfer(token_program.key, ..)`) or the `program_id` field of an
+    /// `Instruction { .. }` literal. A prebuilt `Instruction` passed in via an intermediate
+    /// variable (`let ix = ..; invoke(&ix, ..)`) is not traced back to its `program_id` operand,
+    /// so this lint says nothing about it. Anchor's `Program<'info, T>` is exempted, since its
+    /// `try_from` already checks the account's key.
+    ///
+    /// **Example:**
+    ///
+    /// See https://github.com/coral-xyz/sealevel-attacks/blob/master/programs/8-arbitrary-cpi/insecure/src/lib.rs
+    /// for an insecure example.
+    ///
+    /// Use instead:
+    ///
+    /// See https://github.com/coral-xyz/sealevel-attacks/blob/master/programs/8-arbitrary-cpi/secure/src/lib.rs
+    /// for a secure example.
+    pub ARBITRARY_CPI,
+    Warn,
+    "invoking a cross-program call into a program account whose id is never checked"
+}
+
+impl<'tcx> LateLintPass<'tcx> for ArbitraryCpi {
+    fn check_fn(
+        &mut self,
+        cx: &LateContext<'tcx>,
+        _: FnKind<'tcx>,
+        _: &'tcx FnDecl<'tcx>,
+        body: &'tcx Body<'tcx>,
+        span: Span,
+        _: LocalDefId,
+    ) {
+        if !span.from_expansion() {
+            for account_expr in get_invoked_program_accounts(cx, body) {
+                if !is_safe_program_handle(cx, account_expr) && !contains_key_check(cx, body, account_expr)
+                {
+                    span_lint(
+                        cx,
+                        ARBITRARY_CPI,
+                        account_expr.span,
+                        "this program account is used in a cross-program invocation, but its \
+                         key is never checked against the expected program id",
+                    );
+                }
+            }
+        }
+    }
+}
+
+struct InvokedProgramAccounts<'cx, 'tcx> {
+    cx: &'cx LateContext<'tcx>,
+    accounts: Vec<&'tcx Expr<'tcx>>,
+}
+
+// Walk the body for calls to `invoke`/`invoke_signed`, and for each one, identify the
+// `AccountInfo` whose key is the *program id* operand of the call's `Instruction` argument (not
+// the whole accounts slice, which also carries the accounts the instruction operates on --
+// source, destination, authority, ... -- none of which need a program-id check).
+fn get_invoked_program_accounts<'tcx>(
+    cx: &LateContext<'tcx>,
+    body: &'tcx Body<'tcx>,
+) -> Vec<&'tcx Expr<'tcx>> {
+    let mut accounts = InvokedProgramAccounts {
+        cx,
+        accounts: Vec::new(),
+    };
+    accounts.visit_expr(body.value);
+    accounts.accounts
+}
+
+impl<'cx, 'tcx> Visitor<'tcx> for InvokedProgramAccounts<'cx, 'tcx> {
+    fn visit_expr(&mut self, expr: &'tcx Expr<'tcx>) {
+        if_chain! {
+            if let Some(instruction_arg) = invoke_instruction_arg(self.cx, expr);
+            if let Some(account_expr) = program_id_account(self.cx, instruction_arg);
+            then {
+                let mut spanless_eq = SpanlessEq::new(self.cx);
+                if !self.accounts.iter().any(|e| spanless_eq.eq_expr(e, account_expr)) {
+                    self.accounts.push(account_expr);
+                }
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// If `expr` is a call to `invoke`/`invoke_signed`, return the expression for its `Instruction`
+/// argument.
+fn invoke_instruction_arg<'tcx>(cx: &LateContext<'tcx>, expr: &Expr<'tcx>) -> Option<&'tcx Expr<'tcx>> {
+    if_chain! {
+        if let ExprKind::Call(callee, args) = expr.kind;
+        if let ExprKind::Path(qpath) = &callee.kind;
+        if let Some(def_id) = cx.qpath_res(qpath, callee.hir_id).opt_def_id();
+        if match_any_def_paths(
+            cx,
+            def_id,
+            &[&paths::SOLANA_PROGRAM_INVOKE, &paths::SOLANA_PROGRAM_INVOKE_SIGNED],
+        )
+        .is_some();
+        if let Some(instruction_arg) = args.first();
+        then {
+            Some(*instruction_arg)
+        } else {
+            None
+        }
+    }
+}
+
+/// If `instruction_arg` is (a reference to, or the `?`/`.unwrap()`-result of) a call to an
+/// instruction-builder function, or an `Instruction { .. }` struct literal, isolate the single
+/// operand that supplies the `program_id`: the builder's first argument, or the literal's
+/// `program_id` field. Returns the `AccountInfo` whose key that operand is, if any.
+fn program_id_account<'tcx>(
+    cx: &LateContext<'tcx>,
+    instruction_arg: &'tcx Expr<'tcx>,
+) -> Option<&'tcx Expr<'tcx>> {
+    let instruction_arg = peel_unwrap_and_try(peel_refs(instruction_arg));
+    let program_id_expr = match instruction_arg.kind {
+        ExprKind::Call(_, args) => *args.first()?,
+        ExprKind::Struct(_, fields, _) => {
+            fields.iter().find(|f| f.ident.as_str() == "program_id")?.expr
+        }
+        _ => return None,
+    };
+    key_access_receiver(cx, peel_refs(program_id_expr))
+}
+
+/// Peels a trailing `?`/`.unwrap()`/`.expect(..)` off of `expr`.
+fn peel_unwrap_and_try<'tcx>(mut expr: &'tcx Expr<'tcx>) -> &'tcx Expr<'tcx> {
+    loop {
+        expr = match expr.kind {
+            // `x?` lowers to `match Try::branch(x) { .. }`; take `x` out of the `branch` call.
+            ExprKind::Match(scrutinee, _, MatchSource::TryDesugar(_)) => match scrutinee.kind {
+                ExprKind::Call(_, [branch_arg]) => branch_arg,
+                _ => scrutinee,
+            },
+            ExprKind::MethodCall(segment, recv, _, _)
+                if matches!(segment.ident.as_str(), "unwrap" | "expect") =>
+            {
+                recv
+            }
+            _ => return expr,
+        };
+    }
+}
+
+fn peel_refs<'tcx>(mut expr: &'tcx Expr<'tcx>) -> &'tcx Expr<'tcx> {
+    while let ExprKind::AddrOf(_, _, inner) = expr.kind {
+        expr = inner;
+    }
+    expr
+}
+
+/// If `expr` is `account.key`/`account.key()` for some `AccountInfo`-typed `account`, return
+/// `account`.
+fn key_access_receiver<'tcx>(cx: &LateContext<'tcx>, expr: &Expr<'tcx>) -> Option<&'tcx Expr<'tcx>> {
+    if_chain! {
+        if let ExprKind::Field(object, field_name) = expr.kind;
+        if field_name.as_str() == "key";
+        if match_type(cx, cx.typeck_results().expr_ty(object), &paths::SOLANA_PROGRAM_ACCOUNT_INFO);
+        then {
+            return Some(object);
+        }
+    }
+    if_chain! {
+        if let Some(recv) = is_expr_method_call(cx, expr, &paths::ANCHOR_LANG_KEY);
+        if match_type(cx, cx.typeck_results().expr_ty(recv), &paths::SOLANA_PROGRAM_ACCOUNT_INFO);
+        then {
+            Some(recv)
+        } else {
+            None
+        }
+    }
+}
+
+// smoelius: `Program<'info, T>`'s implementation of `try_from` already checks the account's key
+// against `T::id()`, so there is no ambiguity about which program is being invoked.
+fn is_safe_program_handle<'tcx>(cx: &LateContext<'tcx>, expr: &'tcx Expr<'tcx>) -> bool {
+    if_chain! {
+        if let Some(recv) = is_expr_method_call(cx, expr, &paths::ANCHOR_LANG_TO_ACCOUNT_INFO);
+        if let ty::Ref(_, recv_ty, _) = cx.typeck_results().expr_ty_adjusted(recv).kind();
+        if let ty::Adt(adt_def, _) = recv_ty.kind();
+        if match_def_path(cx, adt_def.did(), &paths::ANCHOR_LANG_PROGRAM);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// if `expr` is a method call of `def_path` return the receiver else None
+fn is_expr_method_call<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    def_path: &[&str],
+) -> Option<&'tcx Expr<'tcx>> {
+    if_chain! {
+        if let ExprKind::MethodCall(_, recv, _, _) = expr.kind;
+        if let Some(def_id) = cx.typeck_results().type_dependent_def_id(expr.hir_id);
+        if match_def_path(cx, def_id, def_path);
+        then {
+            Some(recv)
+        } else {
+            None
+        }
+    }
+}
+
+fn calls_method_on_expr<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    account_expr: &Expr<'tcx>,
+    def_path: &[&str],
+) -> bool {
+    if_chain! {
+        if let Some(recv) = is_expr_method_call(cx, expr, def_path);
+        let mut spanless_eq = SpanlessEq::new(cx);
+        if spanless_eq.eq_expr(account_expr, recv);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn uses_given_field<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    account_expr: &Expr<'tcx>,
+    field: &str,
+) -> bool {
+    if_chain! {
+        if let ExprKind::Field(object, field_name) = expr.kind;
+        if field_name.as_str() == field;
+        let mut spanless_eq = SpanlessEq::new(cx);
+        if spanless_eq.eq_expr(account_expr, object);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Return true if the expr accesses key of account_expr(AccountInfo)
+fn expr_accesses_key<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    account_expr: &Expr<'tcx>,
+) -> bool {
+    // Anchor AccountInfo: `.key()` and Solana AccountInfo: `.key` field.
+    calls_method_on_expr(cx, expr, account_expr, &paths::ANCHOR_LANG_KEY)
+        || uses_given_field(cx, expr, account_expr, "key")
+}
+
+fn contains_key_check<'tcx>(
+    cx: &LateContext<'tcx>,
+    body: &'tcx Body<'tcx>,
+    account_expr: &Expr<'tcx>,
+) -> bool {
+    visit_expr_no_bodies(body.value, |expr| compares_key(cx, expr, account_expr))
+}
+
+fn compares_key<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    account_expr: &Expr<'tcx>,
+) -> bool {
+    if_chain! {
+        if let ExprKind::Binary(op, lhs, rhs) = expr.kind;
+        if matches!(op.node, BinOpKind::Eq | BinOpKind::Ne);
+        if expr_accesses_key(cx, lhs, account_expr) || expr_accesses_key(cx, rhs, account_expr);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -1,6 +1,7 @@
 #![feature(rustc_private)]
 #![warn(unused_extern_crates)]
 
+extern crate rustc_ast;
 extern crate rustc_hir;
 extern crate rustc_middle;
 extern crate rustc_span;
@@ -9,6 +10,7 @@ use clippy_utils::{
     diagnostics::span_lint, match_any_def_paths, match_def_path, ty::match_type, SpanlessEq,
 };
 use if_chain::if_chain;
+use rustc_ast::ast::{AttrArgs, AttrKind};
 use rustc_hir::{
     def_id::LocalDefId,
     intravisit::{walk_expr, FnKind, Visitor},
@@ -16,8 +18,29 @@ use rustc_hir::{
 };
 use rustc_lint::{LateContext, LateLintPass};
 use rustc_middle::ty;
-use rustc_span::Span;
+use rustc_span::{Span, Symbol};
+use serde::Deserialize;
 use solana_lints::{paths, utils::visit_expr_no_bodies};
+use std::sync::OnceLock;
+
+/// Configuration for this lint, read from the `missing_owner_check` table of `dylint.toml`:
+///
+/// ```toml
+/// [missing_owner_check]
+/// allow_plain_owner_reference = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Config {
+    /// Accept a plain reference to the owner field (`account.owner`) as satisfying the check,
+    /// without requiring a comparison. Off by default.
+    allow_plain_owner_reference: bool,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| dylint_linting::config_or_default(env!("CARGO_PKG_NAME")))
+}
 
 dylint_linting::declare_late_lint! {
     /// **What it does:**
@@ -41,8 +64,13 @@ dylint_linting::declare_late_lint! {
     ///
     /// **Known problems:**
     ///
-    /// Key checks can be strengthened. Currently, the lint only checks that the account's owner
-    /// field is referenced somewhere, ie, `AccountInfo.owner`.
+    /// By default, the lint requires the account's owner field to appear as an operand of an
+    /// `==`/`!=` comparison, ie, `account.owner != program_id`. Setting
+    /// `allow_plain_owner_reference = true` in this lint's `dylint.toml` table falls back to the
+    /// old, weaker behavior of accepting any reference to the owner field, ie, `account.owner`,
+    /// even one that is never compared against anything. An `AccountInfo`/`UncheckedAccount`
+    /// field carrying an explicit Anchor `#[account(owner = <expr>)]` constraint is treated as
+    /// checked, since Anchor generates the comparison itself.
     ///
     /// **Example:**
     ///
@@ -71,7 +99,7 @@ impl<'tcx> LateLintPass<'tcx> for MissingOwnerCheck {
         if !span.from_expansion() {
             let accounts = get_referenced_accounts(cx, body);
             for account_expr in accounts {
-                if !contains_owner_use(cx, body, account_expr)
+                if !contains_owner_check(cx, body, account_expr)
                     && !contains_key_check(cx, body, account_expr)
                 {
                     span_lint(
@@ -179,6 +207,67 @@ fn is_safe_to_account_info<'tcx>(cx: &LateContext<'tcx>, expr: &'tcx Expr<'tcx>)
             false
         }
     }
+    || has_owner_constraint(cx, expr)
+}
+
+// smoelius: Anchor's `#[account(owner = <expr>)]` constraint lets a program specify an external
+// owner on an otherwise-unchecked `AccountInfo`/`UncheckedAccount` field. Anchor's generated
+// comparison for this constraint lives in the `Accounts` impl's macro-expanded `try_accounts`
+// function, which this lint never visits (its span comes from expansion, and is skipped by the
+// `from_expansion` check in `check_fn` besides). So instead of looking for the generated check,
+// we detect the constraint directly: the `#[account(...)]` helper attribute is retained on the
+// field in the expanded HIR.
+fn has_owner_constraint<'tcx>(cx: &LateContext<'tcx>, expr: &'tcx Expr<'tcx>) -> bool {
+    let field_expr = is_expr_method_call(cx, expr, &paths::ANCHOR_LANG_TO_ACCOUNT_INFO).unwrap_or(expr);
+    if_chain! {
+        if let ExprKind::Field(object, field_name) = field_expr.kind;
+        // `ctx.accounts` is a reference to the accounts struct; look through it.
+        let object_ty = cx.typeck_results().expr_ty(object).peel_refs();
+        if let ty::Adt(adt_def, _) = object_ty.kind();
+        if let Some(field_def) = adt_def
+            .non_enum_variant()
+            .fields
+            .iter()
+            .find(|field_def| field_def.name.as_str() == field_name.as_str());
+        if cx
+            .tcx
+            .get_attrs(field_def.did, Symbol::intern("account"))
+            .any(attr_constrains_owner);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Checks whether an `#[account(...)]` helper attribute contains an `owner = ...` constraint.
+/// This looks for the exact two-token sequence `owner` `=`, rather than a bare `owner` ident, so
+/// that unrelated uses of that identifier (eg, `has_one = owner`, `close = owner`) are not
+/// mistaken for the constraint.
+fn attr_constrains_owner(attr: &rustc_ast::ast::Attribute) -> bool {
+    if_chain! {
+        if let AttrKind::Normal(normal) = &attr.kind;
+        if let AttrArgs::Delimited(args) = &normal.item.args;
+        then {
+            let trees: Vec<_> = args.tokens.trees().collect();
+            trees.windows(2).any(|pair| {
+                let is_owner_ident = matches!(
+                    pair[0],
+                    rustc_ast::tokenstream::TokenTree::Token(token, _)
+                        if token.is_ident_named(Symbol::intern("owner"))
+                );
+                let is_eq = matches!(
+                    pair[1],
+                    rustc_ast::tokenstream::TokenTree::Token(token, _)
+                        if token.kind == rustc_ast::token::TokenKind::Eq
+                );
+                is_owner_ident && is_eq
+            })
+        } else {
+            false
+        }
+    }
 }
 
 /// if `expr` is a method call of `def_path` return the receiver else None
@@ -199,14 +288,58 @@ fn is_expr_method_call<'tcx>(
     }
 }
 
+// smoelius: `contains_owner_check` is the entry point used by `check_fn`. By default, it demands
+// an actual comparison against the owner field (see `compares_owner`), since a dead reference
+// like `let _ = account.owner;` does nothing to guard against a spoofed account. The weaker,
+// reference-only behavior is kept around as an opt-in fallback for users not yet ready for the
+// stricter check.
+fn contains_owner_check<'tcx>(
+    cx: &LateContext<'tcx>,
+    body: &'tcx Body<'tcx>,
+    account_expr: &Expr<'tcx>,
+) -> bool {
+    contains_owner_comparison(cx, body, account_expr)
+        || (allow_plain_owner_reference() && contains_owner_use(cx, body, account_expr))
+}
+
+fn allow_plain_owner_reference() -> bool {
+    config().allow_plain_owner_reference
+}
+
+fn contains_owner_comparison<'tcx>(
+    cx: &LateContext<'tcx>,
+    body: &'tcx Body<'tcx>,
+    account_expr: &Expr<'tcx>,
+) -> bool {
+    visit_expr_no_bodies(body.value, |expr| compares_owner(cx, expr, account_expr))
+}
+
+fn compares_owner<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    account_expr: &Expr<'tcx>,
+) -> bool {
+    if_chain! {
+        // check if the expr is a comparison expression
+        if let ExprKind::Binary(op, lhs, rhs) = expr.kind;
+        // == or !=
+        if matches!(op.node, BinOpKind::Eq | BinOpKind::Ne);
+        // check if lhs or rhs accesses the owner of `account_expr`
+        if expr_accesses_owner(cx, lhs, account_expr) || expr_accesses_owner(cx, rhs, account_expr);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
+
 fn contains_owner_use<'tcx>(
     cx: &LateContext<'tcx>,
     body: &'tcx Body<'tcx>,
     account_expr: &Expr<'tcx>,
 ) -> bool {
-    visit_expr_no_bodies(body.value, |expr| {
-        uses_given_field(cx, expr, account_expr, "owner")
-    })
+    visit_expr_no_bodies(body.value, |expr| expr_accesses_owner(cx, expr, account_expr))
 }
 
 /// Checks if `expr` is references `field` on `account_expr`
@@ -251,6 +384,17 @@ fn calls_method_on_expr<'tcx>(
     }
 }
 
+// Return true if the expr accesses the owner of account_expr(AccountInfo)
+fn expr_accesses_owner<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    account_expr: &Expr<'tcx>,
+) -> bool {
+    // Solana AccountInfo: `.owner` field and the newer `.owner()` method.
+    calls_method_on_expr(cx, expr, account_expr, &paths::SOLANA_PROGRAM_ACCOUNT_INFO_OWNER)
+        || uses_given_field(cx, expr, account_expr, "owner")
+}
+
 // Return true if the expr access key of account_expr(AccountInfo)
 fn expr_accesses_key<'tcx>(
     cx: &LateContext<'tcx>,
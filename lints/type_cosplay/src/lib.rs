@@ -0,0 +1,287 @@
+#![feature(rustc_private)]
+#![warn(unused_extern_crates)]
+
+extern crate rustc_hir;
+extern crate rustc_middle;
+extern crate rustc_span;
+
+use clippy_utils::{diagnostics::span_lint, match_def_path, ty::match_type, SpanlessEq};
+use if_chain::if_chain;
+use rustc_hir::{
+    def::Res,
+    def_id::LocalDefId,
+    intravisit::{walk_expr, walk_local, FnKind, Visitor},
+    BinOpKind, Body, Expr, ExprKind, FnDecl, HirId, Local, MatchSource, PatKind, QPath,
+};
+use rustc_lint::{LateContext, LateLintPass};
+use rustc_middle::ty;
+use rustc_span::Span;
+use solana_lints::{paths, utils::visit_expr_no_bodies};
+
+dylint_linting::declare_late_lint! {
+    /// **What it does:**
+    ///
+    /// This lint checks that an account's data is discriminated against its expected type
+    /// before the data is deserialized into a Rust struct.
+    ///
+    /// **Why is this bad?**
+    ///
+    /// The type-cosplay vulnerability occurs when two account structs serialize to the same (or
+    /// a compatible) byte layout. If a program deserializes account data without first checking
+    /// that the account actually holds the type it expects, an attacker can pass in an account
+    /// of a different, cosplaying type and have the program operate on it as though it were the
+    /// expected type.
+    ///
+    /// **Known problems:**
+    ///
+    /// This lint only recognizes the `let binding = T::try_from_slice(..)?;` pattern (optionally
+    /// through a trailing `.unwrap()`/`.expect(..)`); a deserialized value that is compared
+    /// inline, without first being bound to a variable, is not tracked. A discriminator check is
+    /// recognized either as an `==`/`!=` comparison on the bound value's first declared field (by
+    /// position, not name, since programs name it `discriminator`/`account_type`/`discriminant`/
+    /// etc.), or as a comparison against a leading slice of the account's raw, not-yet-deserialized
+    /// `.data` (e.g. `account.data.borrow()[..8] == ANCHOR_DISCRIMINATOR`). This lint only examines
+    /// manual deserialization of an `AccountInfo`'s raw
+    /// `.data`; Anchor's `Account<'info, T>` never appears in that position (Anchor deserializes
+    /// it elsewhere, already discriminated by the `#[account]` macro), so it is out of scope
+    /// rather than something this lint needs to exempt.
+    ///
+    /// **Example:**
+    ///
+    /// See https://github.com/coral-xyz/sealevel-attacks/blob/master/programs/4-type-cosplay/insecure/src/lib.rs
+    /// for an insecure example.
+    ///
+    /// Use instead:
+    ///
+    /// See https://github.com/coral-xyz/sealevel-attacks/blob/master/programs/4-type-cosplay/secure/src/lib.rs
+    /// for a secure example.
+    pub TYPE_COSPLAY,
+    Warn,
+    "deserializing account data without checking a discriminator first"
+}
+
+impl<'tcx> LateLintPass<'tcx> for TypeCosplay {
+    fn check_fn(
+        &mut self,
+        cx: &LateContext<'tcx>,
+        _: FnKind<'tcx>,
+        _: &'tcx FnDecl<'tcx>,
+        body: &'tcx Body<'tcx>,
+        span: Span,
+        _: LocalDefId,
+    ) {
+        if !span.from_expansion() {
+            for (account_expr, binding_hir_id) in get_deserialized_bindings(cx, body) {
+                if !contains_discriminator_check(cx, body, account_expr, binding_hir_id) {
+                    span_lint(
+                        cx,
+                        TYPE_COSPLAY,
+                        account_expr.span,
+                        "this account's data is deserialized without checking a discriminator \
+                         first; a cosplaying account of a different type could be passed in",
+                    );
+                }
+            }
+        }
+    }
+}
+
+struct DeserializedBindings<'cx, 'tcx> {
+    cx: &'cx LateContext<'tcx>,
+    // (the `AccountInfo` expr, for diagnostics; the hir_id of the local bound to the
+    // deserialized value, for finding its discriminator check)
+    bindings: Vec<(&'tcx Expr<'tcx>, HirId)>,
+}
+
+// Walk the body for `let binding = T::try_from_slice(&account.data.borrow())?;`-shaped locals
+// (optionally through a trailing `.unwrap()`/`.expect(..)`), and collect the account whose data
+// was deserialized together with the hir_id of `binding`.
+fn get_deserialized_bindings<'tcx>(
+    cx: &LateContext<'tcx>,
+    body: &'tcx Body<'tcx>,
+) -> Vec<(&'tcx Expr<'tcx>, HirId)> {
+    let mut bindings = DeserializedBindings {
+        cx,
+        bindings: Vec::new(),
+    };
+    bindings.visit_expr(body.value);
+    bindings.bindings
+}
+
+impl<'cx, 'tcx> Visitor<'tcx> for DeserializedBindings<'cx, 'tcx> {
+    fn visit_local(&mut self, local: &'tcx Local<'tcx>) {
+        if_chain! {
+            if let Some(init) = local.init;
+            let init = peel_unwrap_and_try(init);
+            if let Some(arg) = deserialize_call_arg(self.cx, init);
+            if let Some(account_expr) = data_borrow_account(self.cx, arg);
+            if let PatKind::Binding(_, hir_id, _, _) = local.pat.kind;
+            then {
+                self.bindings.push((account_expr, hir_id));
+            }
+        }
+        walk_local(self, local);
+    }
+}
+
+/// Peels a trailing `?`/`.unwrap()`/`.expect(..)` off of `expr`.
+fn peel_unwrap_and_try<'tcx>(mut expr: &'tcx Expr<'tcx>) -> &'tcx Expr<'tcx> {
+    loop {
+        expr = match expr.kind {
+            // `x?` lowers to `match Try::branch(x) { .. }`; take `x` out of the `branch` call.
+            ExprKind::Match(scrutinee, _, MatchSource::TryDesugar(_)) => match scrutinee.kind {
+                ExprKind::Call(_, [branch_arg]) => branch_arg,
+                _ => scrutinee,
+            },
+            ExprKind::MethodCall(segment, recv, _, _)
+                if matches!(segment.ident.as_str(), "unwrap" | "expect") =>
+            {
+                recv
+            }
+            _ => return expr,
+        };
+    }
+}
+
+/// If `expr` is a call to a Borsh deserialize function (`T::try_from_slice(bytes)` or
+/// `T::deserialize(&mut bytes)`), return the argument holding the serialized bytes. Only
+/// `BorshDeserialize` methods are matched; bincode's deserialize functions are not recognized.
+fn deserialize_call_arg<'tcx>(cx: &LateContext<'tcx>, expr: &Expr<'tcx>) -> Option<&'tcx Expr<'tcx>> {
+    if_chain! {
+        if let ExprKind::Call(callee, [arg]) = expr.kind;
+        if let ExprKind::Path(qpath @ QPath::TypeRelative(_, segment)) = &callee.kind;
+        if matches!(segment.ident.as_str(), "try_from_slice" | "deserialize");
+        if let Some(def_id) = cx.qpath_res(qpath, callee.hir_id).opt_def_id();
+        if match_def_path(cx, def_id, &paths::BORSH_TRY_FROM_SLICE)
+            || match_def_path(cx, def_id, &paths::BORSH_DESERIALIZE);
+        then {
+            Some(arg)
+        } else {
+            None
+        }
+    }
+}
+
+/// If `expr` is (a reference to) `account.data.borrow()` for some `AccountInfo`-typed
+/// `account`, return `account`.
+fn data_borrow_account<'tcx>(cx: &LateContext<'tcx>, expr: &'tcx Expr<'tcx>) -> Option<&'tcx Expr<'tcx>> {
+    if_chain! {
+        let expr = peel_refs(expr);
+        if let ExprKind::MethodCall(_, recv, _, _) = expr.kind;
+        if let Some(def_id) = cx.typeck_results().type_dependent_def_id(expr.hir_id);
+        if match_def_path(cx, def_id, &paths::REFCELL_BORROW);
+        if let ExprKind::Field(account_expr, field_name) = recv.kind;
+        if field_name.as_str() == "data";
+        if match_type(
+            cx,
+            cx.typeck_results().expr_ty(account_expr),
+            &paths::SOLANA_PROGRAM_ACCOUNT_INFO,
+        );
+        then {
+            Some(account_expr)
+        } else {
+            None
+        }
+    }
+}
+
+fn peel_refs<'tcx>(mut expr: &'tcx Expr<'tcx>) -> &'tcx Expr<'tcx> {
+    while let ExprKind::AddrOf(_, _, inner) = expr.kind {
+        expr = inner;
+    }
+    expr
+}
+
+fn contains_discriminator_check<'tcx>(
+    cx: &LateContext<'tcx>,
+    body: &'tcx Body<'tcx>,
+    account_expr: &Expr<'tcx>,
+    binding_hir_id: HirId,
+) -> bool {
+    visit_expr_no_bodies(body.value, |expr| {
+        compares_discriminator(cx, expr, account_expr, binding_hir_id)
+    })
+}
+
+/// Checks whether `expr` is an `==`/`!=` comparison that either compares the first field of the
+/// value bound to `binding_hir_id`, or compares a leading slice of `account_expr`'s raw data --
+/// ie, a discriminator check.
+fn compares_discriminator<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    account_expr: &Expr<'tcx>,
+    binding_hir_id: HirId,
+) -> bool {
+    if_chain! {
+        if let ExprKind::Binary(op, lhs, rhs) = expr.kind;
+        if matches!(op.node, BinOpKind::Eq | BinOpKind::Ne);
+        if references_leading_field(cx, lhs, binding_hir_id)
+            || references_leading_field(cx, rhs, binding_hir_id)
+            || references_leading_bytes(cx, lhs, account_expr)
+            || references_leading_bytes(cx, rhs, account_expr);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Checks whether `expr` is `binding.<first field>`, where `binding` is the local identified by
+/// `binding_hir_id` and `<first field>` is the first field declared on its type (by position, not
+/// name, since programs spell the discriminator field `discriminator`/`account_type`/
+/// `discriminant`/etc.).
+fn references_leading_field<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    binding_hir_id: HirId,
+) -> bool {
+    if_chain! {
+        if let ExprKind::Field(object, field_name) = expr.kind;
+        if let ExprKind::Path(QPath::Resolved(None, path)) = object.kind;
+        if let Res::Local(hir_id) = path.res;
+        if hir_id == binding_hir_id;
+        if is_leading_field(cx, binding_hir_id, field_name.as_str());
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Checks whether `field_name` is the first field declared on the type bound to `binding_hir_id`.
+fn is_leading_field<'tcx>(cx: &LateContext<'tcx>, binding_hir_id: HirId, field_name: &str) -> bool {
+    let ty = cx.typeck_results().node_type(binding_hir_id);
+    if_chain! {
+        if let ty::Adt(adt_def, _) = ty.kind();
+        if let Some(first_field) = adt_def.non_enum_variant().fields.iter().next();
+        if first_field.name.as_str() == field_name;
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Checks whether `expr` indexes `account_expr`'s raw borrowed `.data` (eg,
+/// `account_expr.data.borrow()[..8]`), ie, a discriminator check performed directly on the raw
+/// bytes rather than through a deserialized binding.
+fn references_leading_bytes<'tcx>(
+    cx: &LateContext<'tcx>,
+    expr: &Expr<'tcx>,
+    account_expr: &Expr<'tcx>,
+) -> bool {
+    if_chain! {
+        if let ExprKind::Index(base, ..) = expr.kind;
+        if let Some(base_account) = data_borrow_account(cx, base);
+        let mut spanless_eq = SpanlessEq::new(cx);
+        if spanless_eq.eq_expr(account_expr, base_account);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
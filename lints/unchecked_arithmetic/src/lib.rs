@@ -0,0 +1,224 @@
+#![feature(rustc_private)]
+#![warn(unused_extern_crates)]
+
+extern crate rustc_hir;
+extern crate rustc_span;
+
+use clippy_utils::{diagnostics::span_lint, match_def_path, ty::match_type};
+use if_chain::if_chain;
+use rustc_hir::{
+    def_id::LocalDefId,
+    intravisit::{walk_expr, FnKind, Visitor},
+    BinOpKind, Body, Expr, ExprKind, FnDecl, MatchSource, UnOp,
+};
+use rustc_lint::{LateContext, LateLintPass};
+use rustc_span::Span;
+use serde::Deserialize;
+use solana_lints::paths;
+use std::sync::OnceLock;
+
+/// Configuration for this lint, read from the `unchecked_arithmetic` table of `dylint.toml`:
+///
+/// ```toml
+/// [unchecked_arithmetic]
+/// flag_saturating = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Config {
+    /// Also flag `saturating_*` methods on account-derived values, since silently saturating a
+    /// balance can mask a logic error just as badly as wrapping it. Off by default.
+    flag_saturating: bool,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| dylint_linting::config_or_default(env!("CARGO_PKG_NAME")))
+}
+
+dylint_linting::declare_late_lint! {
+    /// **What it does:**
+    ///
+    /// This lint checks for arithmetic performed with the raw `+`, `-`, `*`, `/` operators on
+    /// values derived from an account's balance/amount/lamports fields.
+    ///
+    /// **Why is this bad?**
+    ///
+    /// Per Solana's security guidance, arithmetic on token balances or lamports using the raw
+    /// operators can silently overflow or underflow in release mode, since overflow checks are
+    /// disabled outside of debug builds. The `checked_add`/`checked_sub`/`checked_mul`/
+    /// `checked_div` family of methods make the failure explicit instead.
+    ///
+    /// **Known problems:**
+    ///
+    /// This lint only recognizes a `balance`/`amount`/`lamports` field read when the field's
+    /// receiver is, through typeck, an SPL `TokenAccount`, a `solana_program::AccountInfo`, or an
+    /// Anchor `Account<'info, T>` wrapping one of those — so an unrelated struct that happens to
+    /// have a same-named field is not flagged. It also recognizes the `**account.lamports
+    /// .borrow()`/`.try_borrow()` lamports-deref pattern. Arithmetic on values that have been
+    /// copied into unrelated local variables is not traced. Setting `flag_saturating = true` in
+    /// this lint's `dylint.toml` table additionally flags the `saturating_*` methods in the same
+    /// positions, since silently saturating a balance can mask a logic error just as badly as
+    /// wrapping it.
+    ///
+    /// **Example:**
+    ///
+    /// ```rust
+    /// account.amount = account.amount - transfer_amount;
+    /// ```
+    ///
+    /// Use instead:
+    ///
+    /// ```rust
+    /// account.amount = account
+    ///     .amount
+    ///     .checked_sub(transfer_amount)
+    ///     .ok_or(ErrorCode::Overflow)?;
+    /// ```
+    pub UNCHECKED_ARITHMETIC,
+    Warn,
+    "using raw arithmetic operators on account balance or lamport values"
+}
+
+impl<'tcx> LateLintPass<'tcx> for UncheckedArithmetic {
+    fn check_fn(
+        &mut self,
+        cx: &LateContext<'tcx>,
+        _: FnKind<'tcx>,
+        _: &'tcx FnDecl<'tcx>,
+        body: &'tcx Body<'tcx>,
+        span: Span,
+        _: LocalDefId,
+    ) {
+        if !span.from_expansion() {
+            let mut visitor = ArithmeticVisitor { cx };
+            visitor.visit_expr(body.value);
+        }
+    }
+}
+
+struct ArithmeticVisitor<'cx, 'tcx> {
+    cx: &'cx LateContext<'tcx>,
+}
+
+impl<'cx, 'tcx> Visitor<'tcx> for ArithmeticVisitor<'cx, 'tcx> {
+    fn visit_expr(&mut self, expr: &'tcx Expr<'tcx>) {
+        if_chain! {
+            if let ExprKind::Binary(op, lhs, rhs) = expr.kind;
+            if matches!(
+                op.node,
+                BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul | BinOpKind::Div
+            );
+            if is_integer_typed(self.cx, expr);
+            if is_account_derived(self.cx, lhs) || is_account_derived(self.cx, rhs);
+            then {
+                span_lint(
+                    self.cx,
+                    UNCHECKED_ARITHMETIC,
+                    expr.span,
+                    &format!(
+                        "this arithmetic on an account balance uses a raw operator; use \
+                         `checked_{}` instead",
+                        checked_method_name(op.node),
+                    ),
+                );
+            }
+        }
+
+        if_chain! {
+            if flag_saturating();
+            if let ExprKind::MethodCall(segment, recv, _, _) = expr.kind;
+            if segment.ident.as_str().starts_with("saturating_");
+            if is_integer_typed(self.cx, expr);
+            if is_account_derived(self.cx, recv);
+            then {
+                span_lint(
+                    self.cx,
+                    UNCHECKED_ARITHMETIC,
+                    expr.span,
+                    "this arithmetic on an account balance saturates instead of checking for \
+                     overflow; a saturated value can mask a logic error",
+                );
+            }
+        }
+
+        walk_expr(self, expr);
+    }
+}
+
+fn flag_saturating() -> bool {
+    config().flag_saturating
+}
+
+fn checked_method_name(op: BinOpKind) -> &'static str {
+    match op {
+        BinOpKind::Add => "add",
+        BinOpKind::Sub => "sub",
+        BinOpKind::Mul => "mul",
+        BinOpKind::Div => "div",
+        _ => unreachable!("only called for Add | Sub | Mul | Div"),
+    }
+}
+
+fn is_integer_typed<'tcx>(cx: &LateContext<'tcx>, expr: &Expr<'tcx>) -> bool {
+    cx.typeck_results().expr_ty(expr).is_integral()
+}
+
+/// Checks whether `expr` derives from an account's balance/amount/lamports, either as a field
+/// read (`account.amount`) or as a lamports deref (`**account.lamports.borrow()`).
+fn is_account_derived<'tcx>(cx: &LateContext<'tcx>, expr: &Expr<'tcx>) -> bool {
+    let expr = peel_integer_casts(expr);
+    if_chain! {
+        if let ExprKind::Field(object, field_name) = expr.kind;
+        if matches!(field_name.as_str(), "balance" | "amount" | "lamports");
+        if is_account_typed(cx, object);
+        then {
+            return true;
+        }
+    }
+    is_lamports_deref(cx, expr)
+}
+
+/// Checks whether `expr`'s type is an SPL `TokenAccount`, a raw `AccountInfo`, or an Anchor
+/// `Account<'info, T>` wrapping one of those.
+fn is_account_typed<'tcx>(cx: &LateContext<'tcx>, expr: &Expr<'tcx>) -> bool {
+    let ty = cx.typeck_results().expr_ty_adjusted(expr).peel_refs();
+    match_type(cx, ty, &paths::SPL_TOKEN_ACCOUNT)
+        || match_type(cx, ty, &paths::SOLANA_PROGRAM_ACCOUNT_INFO)
+        || match_type(cx, ty, &paths::ANCHOR_LANG_ACCOUNT)
+}
+
+fn peel_integer_casts<'tcx>(mut expr: &'tcx Expr<'tcx>) -> &'tcx Expr<'tcx> {
+    while let ExprKind::Cast(inner, _) = expr.kind {
+        expr = inner;
+    }
+    expr
+}
+
+/// Checks whether `expr` is `**account.lamports.borrow()` or `**account.lamports.try_borrow()?`.
+fn is_lamports_deref<'tcx>(cx: &LateContext<'tcx>, expr: &Expr<'tcx>) -> bool {
+    if_chain! {
+        if let ExprKind::Unary(UnOp::Deref, inner) = expr.kind;
+        if let ExprKind::Unary(UnOp::Deref, inner) = inner.kind;
+        // `x?` lowers to `match Try::branch(x) { .. }`; take `x` out of the `branch` call.
+        let inner = match inner.kind {
+            ExprKind::Match(scrutinee, _, MatchSource::TryDesugar(_)) => match scrutinee.kind {
+                ExprKind::Call(_, [branch_arg]) => branch_arg,
+                _ => scrutinee,
+            },
+            _ => inner,
+        };
+        if let ExprKind::MethodCall(_, recv, _, _) = inner.kind;
+        if let Some(def_id) = cx.typeck_results().type_dependent_def_id(inner.hir_id);
+        if match_def_path(cx, def_id, &paths::REFCELL_BORROW)
+            || match_def_path(cx, def_id, &paths::REFCELL_TRY_BORROW);
+        if let ExprKind::Field(account_expr, field_name) = recv.kind;
+        if field_name.as_str() == "lamports";
+        if is_account_typed(cx, account_expr);
+        then {
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Shared helpers for the lints in this workspace: common def-path constants and small HIR
+//! traversal utilities that don't belong to any single lint.
+#![feature(rustc_private)]
+#![warn(unused_extern_crates)]
+
+extern crate rustc_hir;
+
+pub mod paths;
+pub mod utils;
@@ -0,0 +1,35 @@
+//! Def-path constants shared by the lints in this workspace, consumed via
+//! `clippy_utils::{match_def_path, match_any_def_paths}` and `clippy_utils::ty::match_type`.
+
+pub const CORE_CLONE: [&str; 4] = ["core", "clone", "Clone", "clone"];
+
+pub const ANCHOR_LANG_KEY: [&str; 3] = ["anchor_lang", "Key", "key"];
+pub const ANCHOR_LANG_TO_ACCOUNT_INFO: [&str; 3] =
+    ["anchor_lang", "ToAccountInfo", "to_account_info"];
+pub const ANCHOR_LANG_ACCOUNT: [&str; 4] = ["anchor_lang", "accounts", "account", "Account"];
+pub const ANCHOR_LANG_PROGRAM: [&str; 4] = ["anchor_lang", "accounts", "program", "Program"];
+pub const ANCHOR_LANG_SYSTEM_ACCOUNT: [&str; 4] =
+    ["anchor_lang", "accounts", "system_account", "SystemAccount"];
+pub const ANCHOR_LANG_ACCOUNT_LOADER: [&str; 4] =
+    ["anchor_lang", "accounts", "account_loader", "AccountLoader"];
+pub const ANCHOR_LANG_SIGNER: [&str; 4] = ["anchor_lang", "accounts", "signer", "Signer"];
+pub const ANCHOR_LANG_SYSVAR: [&str; 4] = ["anchor_lang", "accounts", "sysvar", "Sysvar"];
+
+pub const SOLANA_PROGRAM_ACCOUNT_INFO: [&str; 3] =
+    ["solana_program", "account_info", "AccountInfo"];
+// smoelius: `owner()` is the `AccountInfo` accessor method, not an Anchor trait method; it
+// resolves on `solana_program::account_info::AccountInfo`, not `anchor_lang`.
+pub const SOLANA_PROGRAM_ACCOUNT_INFO_OWNER: [&str; 4] =
+    ["solana_program", "account_info", "AccountInfo", "owner"];
+pub const SOLANA_PROGRAM_INVOKE: [&str; 3] = ["solana_program", "program", "invoke"];
+pub const SOLANA_PROGRAM_INVOKE_SIGNED: [&str; 3] =
+    ["solana_program", "program", "invoke_signed"];
+
+pub const BORSH_TRY_FROM_SLICE: [&str; 4] =
+    ["borsh", "de", "BorshDeserialize", "try_from_slice"];
+pub const BORSH_DESERIALIZE: [&str; 4] = ["borsh", "de", "BorshDeserialize", "deserialize"];
+
+pub const REFCELL_BORROW: [&str; 4] = ["core", "cell", "RefCell", "borrow"];
+pub const REFCELL_TRY_BORROW: [&str; 4] = ["core", "cell", "RefCell", "try_borrow"];
+
+pub const SPL_TOKEN_ACCOUNT: [&str; 3] = ["spl_token", "state", "Account"];
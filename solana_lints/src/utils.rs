@@ -0,0 +1,42 @@
+use rustc_hir::{
+    intravisit::{walk_expr, Visitor},
+    Expr,
+};
+
+struct NoBodiesVisitor<'tcx, F> {
+    predicate: F,
+    found: bool,
+    _marker: std::marker::PhantomData<&'tcx ()>,
+}
+
+impl<'tcx, F> Visitor<'tcx> for NoBodiesVisitor<'tcx, F>
+where
+    F: FnMut(&Expr<'tcx>) -> bool,
+{
+    fn visit_expr(&mut self, expr: &'tcx Expr<'tcx>) {
+        if self.found {
+            return;
+        }
+        if (self.predicate)(expr) {
+            self.found = true;
+            return;
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// Walks `expr` and returns `true` if `predicate` holds for some sub-expression, without
+/// descending into the bodies of nested closures (the default behavior of `intravisit::Visitor`,
+/// which does not follow `ExprKind::Closure` into its `Body` unless asked to).
+pub fn visit_expr_no_bodies<'tcx>(
+    expr: &'tcx Expr<'tcx>,
+    predicate: impl FnMut(&Expr<'tcx>) -> bool,
+) -> bool {
+    let mut visitor = NoBodiesVisitor {
+        predicate,
+        found: false,
+        _marker: std::marker::PhantomData,
+    };
+    visitor.visit_expr(expr);
+    visitor.found
+}